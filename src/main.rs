@@ -1,25 +1,46 @@
-use bitvec::{bitvec, vec::BitVec};
 use clap::Parser;
 use itertools::Itertools;
-use rand::prelude::*;
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
 use std::fs;
-use std::io;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::sync::Arc;
+use unidecode::unidecode;
+use wide::u32x8;
 
 #[derive(Parser)]
 struct Options {
     #[clap(name = "WORDS")]
     words: PathBuf,
+
+    /// Use a rarest-letter-first branch-and-bound search instead of the plain depth-first search.
+    ///
+    /// Specialized for the "N words covering all but a few letters of the alphabet" goal: at each
+    /// step we are forced to either consume or permanently skip the least-frequent letter not yet
+    /// accounted for, which collapses the branching factor down to the handful of words
+    /// containing that letter.
+    #[clap(long)]
+    rarest_first: bool,
+
+    /// Where to write the solutions, one group per line. Defaults to stdout.
+    #[clap(long)]
+    output: Option<PathBuf>,
+
+    /// Length of each word in a group. Defaults to the classic Quordle word length of 5.
+    #[clap(long, default_value_t = 5)]
+    word_len: usize,
+
+    /// Number of words in a complete group. Defaults to the classic Quordle group size of 5.
+    #[clap(long, default_value_t = 5)]
+    group_size: usize,
 }
 
 #[derive(Clone)]
 struct Word {
     word: String,
-    letters: BitVec,
+    letters: u32,
     index: usize,
 }
 
@@ -60,9 +81,10 @@ impl<'a> Iterator for Words<'a> {
     }
 }
 
+#[derive(Clone)]
 struct WordGroup {
     length: usize,
-    letters: BitVec,
+    letters: u32,
     node: Arc<GroupNode>,
 }
 
@@ -70,7 +92,7 @@ impl WordGroup {
     fn new(word: Word) -> Self {
         Self {
             length: 1,
-            letters: word.letters.clone(),
+            letters: word.letters,
             node: Arc::new(GroupNode { word, parent: None }),
         }
     }
@@ -84,12 +106,12 @@ impl WordGroup {
     }
 
     fn add(&self, word: Word) -> Option<Self> {
-        if (word.letters.clone() & self.letters.clone()).any() {
+        if word.letters & self.letters != 0 {
             return None;
         }
         Some(Self {
             length: 1 + self.length,
-            letters: self.letters.clone() | word.letters.clone(),
+            letters: self.letters | word.letters,
             node: Arc::new(GroupNode {
                 word,
                 parent: Some(self.node.clone()),
@@ -107,28 +129,193 @@ impl Display for WordGroup {
     }
 }
 
-fn word_letters(word: &str) -> BitVec {
-    let mut letters = bitvec![0; 26];
+/// Packs the distinct letters of `word` into a `u32`, bit *i* set iff letter *i* (`'a'` = 0)
+/// occurs in the word.
+fn word_letters(word: &str) -> u32 {
+    let mut letters = 0u32;
     for letter in word.chars() {
-        letters.set(letter as usize - 'a' as usize, true);
+        letters |= 1 << (letter as u8 - b'a');
     }
     letters
 }
 
+/// Filters `candidates` (word indices into `letters`) down to those whose letters are disjoint
+/// from `mask`, testing 8 candidates at a time with SIMD before falling back to scalar checks on
+/// the remainder. This is the hot path in both building the `heterogrammic` adjacency and
+/// extending a group during search, so it is worth batching.
+fn disjoint_candidates(mask: u32, candidates: &[usize], letters: &[u32]) -> Vec<usize> {
+    let mut out = Vec::with_capacity(candidates.len());
+    let mut chunks = candidates.chunks_exact(8);
+    let broadcast = u32x8::splat(mask);
+    for chunk in &mut chunks {
+        let batch: [u32; 8] = std::array::from_fn(|i| letters[chunk[i]]);
+        let anded: [u32; 8] = (u32x8::new(batch) & broadcast).into();
+        for (k, &bits) in anded.iter().enumerate() {
+            if bits == 0 {
+                out.push(chunk[k]);
+            }
+        }
+    }
+    for &j in chunks.remainder() {
+        if mask & letters[j] == 0 {
+            out.push(j);
+        }
+    }
+    out
+}
+
+/// Depth-first enumeration of heterogrammic groups, modeled on the classic anagram-recursion
+/// pattern. `group` is the partial group built so far; only words with index `>= start` are
+/// considered for the next slot, so each word is only ever added to a group once (groups
+/// containing the same words in a different order are never both enumerated). Only the path from
+/// the root to the current group lives on the stack at any one time, unlike the old level-by-level
+/// fixpoint, which materialized every group of every length into memory at once.
+fn search(
+    words: &[Word],
+    letters: &[u32],
+    heterogrammic: &[Vec<usize>],
+    group: &WordGroup,
+    start: usize,
+    group_size: usize,
+    out: &mut Vec<WordGroup>,
+) {
+    if group.length == group_size {
+        out.push(group.clone());
+        return;
+    }
+    for j in disjoint_candidates(group.letters, &heterogrammic[group.word().index], letters) {
+        if j < start {
+            continue;
+        }
+        if let Some(next) = group.add(words[j].clone()) {
+            search(words, letters, heterogrammic, &next, j + 1, group_size, out);
+        }
+    }
+}
+
+/// Relabels the 26 letters in ascending order of frequency across `words`, so that bit 0 of a
+/// relabeled mask is always the rarest letter remaining in play. Returns the relabeled masks
+/// (indexed the same way as `words`) and the permutation mapping each new label back to its
+/// original letter (`permutation[new_label] = original_letter`), so callers that need to interpret
+/// a relabeled mask can map it back.
+fn relabel_by_frequency(words: &[Word]) -> (Vec<u32>, [usize; 26]) {
+    let mut frequency = [0usize; 26];
+    for word in words {
+        for (letter, count) in frequency.iter_mut().enumerate() {
+            if word.letters & (1 << letter) != 0 {
+                *count += 1;
+            }
+        }
+    }
+    let mut permutation: [usize; 26] = std::array::from_fn(|letter| letter);
+    permutation.sort_by_key(|&letter| frequency[letter]);
+
+    let mut original_to_new = [0usize; 26];
+    for (new_label, &original) in permutation.iter().enumerate() {
+        original_to_new[original] = new_label;
+    }
+
+    let relabeled = words
+        .iter()
+        .map(|word| {
+            let mut mask = 0u32;
+            for (letter, &new_label) in original_to_new.iter().enumerate() {
+                if word.letters & (1 << letter) != 0 {
+                    mask |= 1 << new_label;
+                }
+            }
+            mask
+        })
+        .collect();
+    (relabeled, permutation)
+}
+
+/// Rarest-letter-first branch-and-bound search for groups that cover all but a few letters of the
+/// alphabet. `relabeled` gives each word's letters in ascending-frequency order (see
+/// `relabel_by_frequency`), so the lowest set bit not in `used | skipped` is always the rarest
+/// letter that hasn't been accounted for yet. Every branch is forced to either consume that letter
+/// (trying each word that contains it) or permanently skip it, up to `max_skips` total skips; this
+/// collapses the fan-out from the whole dictionary to the handful of words containing the current
+/// rarest letter.
+#[allow(clippy::too_many_arguments)]
+fn search_rare_first(
+    words: &[Word],
+    relabeled: &[u32],
+    group: Option<&WordGroup>,
+    used: u32,
+    skipped: u32,
+    max_skips: u32,
+    group_size: usize,
+    out: &mut Vec<WordGroup>,
+) {
+    if group.is_some_and(|g| g.length == group_size) {
+        out.push(group.unwrap().clone());
+        return;
+    }
+
+    let remaining = !(used | skipped) & ((1u32 << 26) - 1);
+    if remaining == 0 {
+        return;
+    }
+    let target = 1u32 << remaining.trailing_zeros();
+
+    if skipped.count_ones() < max_skips {
+        search_rare_first(
+            words,
+            relabeled,
+            group,
+            used,
+            skipped | target,
+            max_skips,
+            group_size,
+            out,
+        );
+    }
+
+    for (j, &mask) in relabeled.iter().enumerate() {
+        if mask & target == 0 || mask & (used | skipped) != 0 {
+            continue;
+        }
+        let next = match group {
+            Some(g) => g.add(words[j].clone()),
+            None => Some(WordGroup::new(words[j].clone())),
+        };
+        if let Some(next) = next {
+            search_rare_first(
+                words,
+                relabeled,
+                Some(&next),
+                used | mask,
+                skipped,
+                max_skips,
+                group_size,
+                out,
+            );
+        }
+    }
+}
+
 fn main() -> io::Result<()> {
     let opt = Options::parse();
     let words: Vec<String> = std::str::from_utf8(&fs::read(&opt.words)?)
         .unwrap()
         .split_whitespace()
-        .map(|w| w.to_lowercase())
+        // Transliterate accented characters down to their closest ASCII letter (e.g. "café" ->
+        // "cafe") before filtering, so word lists in languages other than English aren't almost
+        // entirely discarded by the ASCII-only filter below.
+        .map(|w| unidecode(w).to_lowercase())
         .filter(|w| {
-            w.len() == 5
+            w.len() == opt.word_len
                 && w.chars().all(|c| c.is_ascii_lowercase())
-                && w.chars().unique().count() == 5
+                && w.chars().unique().count() == opt.word_len
         })
         .unique()
         .collect();
-    println!("Found {} 5-letter heterogrammic words", words.len());
+    println!(
+        "Found {} {}-letter heterogrammic words",
+        words.len(),
+        opt.word_len
+    );
 
     // Reduce to one representative of each anagrammic equivalence class. If there exists a
     // heterogrammic group including anagrams, then
@@ -137,7 +324,7 @@ fn main() -> io::Result<()> {
     // Therefore a group of representatives of equivalences classes of words is itself a
     // representative of an equivalence class of groups, and we can recover the full class by
     // permuting the representatives of the anagram classes we include in the group.
-    let mut anagrams: HashMap<BitVec, Vec<String>> = Default::default();
+    let mut anagrams: HashMap<u32, Vec<String>> = Default::default();
     for word in words {
         let letters = word_letters(&word);
         anagrams.entry(letters).or_default().push(word);
@@ -151,66 +338,108 @@ fn main() -> io::Result<()> {
         .map(|(i, w)| Word::new(i, w))
         .collect::<Vec<_>>();
 
-    // Map the index of each word to the indices of all words _after it_ with which it is
-    // heterogrammic. As long as we consider groups starting with each word, we only need to
-    // consider heterogrammic words after a given word `w` when extending a group that contains `w`,
-    // because if there is a word before `w` that extends the group, then the group itself is an
-    // extension of another group, and we will find it that way.
-    let heterogrammic: Vec<HashSet<usize>> = words
-        .iter()
-        .map(|word| {
-            words[word.index + 1..]
-                .iter()
-                .filter_map(|w| {
-                    if (word.letters.clone() & w.letters.clone()).not_any() {
-                        Some(w.index)
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        })
-        .collect();
+    // Flat table of letter masks, indexed the same way as `words`, so hot loops can batch over
+    // plain `u32`s instead of indexing through `Word`.
+    let letters: Vec<u32> = words.iter().map(|w| w.letters).collect();
 
-    // All groups of length 1: the singleton group for each word.
-    let mut groups: Vec<WordGroup> = words.iter().cloned().map(WordGroup::new).collect();
-
-    // Try to extend each group with all possible words, giving all groups of length `i + 1`. We
-    // iterate this process to fixpoint. This is important, even if we are ultimately only
-    // interested in groups of length 5, because we need to extend early groups as long as they will
-    // go to ensure that we find all possible groups, since we only extend groups with words that
-    // come later.
-    for i in 1.. {
-        println!("{} groups of length {}", groups.len(), i);
-        if groups.is_empty() {
-            break;
-        }
-        println!("here is a sampling:");
-        for _ in 0..5 {
-            println!("  {}", groups.choose(&mut thread_rng()).unwrap());
+    let solutions: Vec<WordGroup> = if opt.rarest_first {
+        let (relabeled, permutation) = relabel_by_frequency(&words);
+        println!(
+            "Letters by ascending frequency: {}",
+            permutation
+                .iter()
+                .map(|&letter| (b'a' + letter as u8) as char)
+                .join(", ")
+        );
+        // Exactly `26 - group_size * word_len` letters can be left unused by a complete group;
+        // that's how many times a branch is allowed to skip the current rarest letter. A complete
+        // group can't cover more letters than the alphabet has.
+        let letters_needed = opt.group_size.saturating_mul(opt.word_len);
+        if letters_needed > 26 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "--rarest-first requires group-size * word-len <= 26, got {} * {} = {}",
+                    opt.group_size, opt.word_len, letters_needed
+                ),
+            ));
         }
+        let max_skips = (26 - letters_needed) as u32;
+        let mut out = Vec::new();
+        search_rare_first(
+            &words,
+            &relabeled,
+            None,
+            0,
+            0,
+            max_skips,
+            opt.group_size,
+            &mut out,
+        );
+        out
+    } else {
+        // Map the index of each word to the indices of all words _after it_ with which it is
+        // heterogrammic. As long as we consider groups starting with each word, we only need to
+        // consider heterogrammic words after a given word `w` when extending a group that contains
+        // `w`, because if there is a word before `w` that extends the group, then the group itself
+        // is an extension of another group, and we will find it that way.
+        let heterogrammic: Vec<Vec<usize>> = words
+            .iter()
+            .map(|word| {
+                let candidates: Vec<usize> = (word.index + 1..words.len()).collect();
+                disjoint_candidates(word.letters, &candidates, &letters)
+            })
+            .collect();
 
-        groups = groups
+        // Recursively extend each word into groups of `opt.group_size`, starting a separate
+        // search from each word in parallel. Only the current path through the search (depth <=
+        // group_size) is ever resident in memory, unlike the old fixpoint, which kept every group
+        // of every length alive until the next level was built.
+        words
             .par_iter()
-            .flat_map(|g| {
-                // Find all words which might extend this group. To avoid the expense of trying to
-                // extend the group with every word in the dictionary, we will first only consider
-                // words which are heterogrammic with (and later than) the first word in the group,
-                // and we will then filter this set of words even further by including only words
-                // which are hterogrammic with (and later than) all other words which are already in
-                // the group.
-                let extensions = heterogrammic[g.word().index].par_iter().filter(|&&j| {
-                    g.words().all(|w| j > w.index)
-                        && g.words()
-                            .skip(1)
-                            .all(|w| heterogrammic[w.index].contains(&j))
-                });
-                extensions.filter_map(|&j| match g.add(words[j].clone()) {
-                    Some(g) => Some(g),
-                    None => None,
-                })
+            .map(|word| {
+                let mut out = Vec::new();
+                search(
+                    &words,
+                    &letters,
+                    &heterogrammic,
+                    &WordGroup::new(word.clone()),
+                    word.index + 1,
+                    opt.group_size,
+                    &mut out,
+                );
+                out
             })
-            .collect();
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flatten()
+            .collect()
+    };
+    println!("Found {} equivalence-class solutions", solutions.len());
+
+    // Each solution is a group of anagram-class representatives; expand it back into every
+    // concrete group of real words by taking the Cartesian product of the anagram lists of its
+    // member words. Each anagram class has a single representative, so different solutions
+    // expand to different concrete groups; the list is sorted and deduplicated for output.
+    let mut expanded: Vec<Vec<String>> = Vec::new();
+    for group in &solutions {
+        let choices: Vec<&Vec<String>> = group.words().map(|w| &anagrams[&w.letters]).collect();
+        for combo in choices.into_iter().multi_cartesian_product() {
+            let mut combo: Vec<String> = combo.into_iter().cloned().collect();
+            combo.sort();
+            expanded.push(combo);
+        }
+    }
+    expanded.sort();
+    expanded.dedup();
+    println!("Found {} total solutions", expanded.len());
+
+    let mut output: Box<dyn Write> = match &opt.output {
+        Some(path) => Box::new(fs::File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    for combo in &expanded {
+        writeln!(output, "{}", combo.join(" "))?;
     }
 
     Ok(())